@@ -0,0 +1,316 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{Data, DeriveInput, Fields, Type};
+
+// Per-field options parsed from #[json(...)] attributes.
+struct FieldOpts {
+    rename: Option<String>,
+    default: bool,
+}
+
+fn field_opts(attrs: &[syn::Attribute]) -> FieldOpts {
+    let mut opts = FieldOpts {
+        rename: None,
+        default: false,
+    };
+
+    for attr in attrs {
+        if !attr.path().is_ident("json") {
+            continue;
+        }
+        // #[json(rename = "...")] and #[json(default)]
+        let _ = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("default") {
+                opts.default = true;
+            } else if meta.path.is_ident("rename") {
+                let value = meta.value()?;
+                let s: syn::LitStr = value.parse()?;
+                opts.rename = Some(s.value());
+            }
+            Ok(())
+        });
+    }
+
+    opts
+}
+
+// Whether a field's type is spelled `Option<...>`.
+fn is_option(ty: &Type) -> bool {
+    if let Type::Path(p) = ty {
+        if let Some(seg) = p.path.segments.last() {
+            return seg.ident == "Option";
+        }
+    }
+    false
+}
+
+#[proc_macro_derive(FromJSON, attributes(json))]
+pub fn derive_from_json(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+
+    let body = match &input.data {
+        Data::Struct(s) => from_json_struct(s),
+        Data::Enum(e) => from_json_enum(e),
+        Data::Union(_) => {
+            return syn::Error::new_spanned(name, "FromJSON cannot be derived for unions")
+                .to_compile_error()
+                .into()
+        }
+    };
+
+    let expanded = quote! {
+        impl ::fortunate_json::FromJSON for #name {
+            fn from_json(
+                v: &::fortunate_json::Value,
+            ) -> ::std::result::Result<Self, ::fortunate_json::DecodeError> {
+                #body
+            }
+        }
+    };
+
+    expanded.into()
+}
+
+fn from_json_struct(s: &syn::DataStruct) -> proc_macro2::TokenStream {
+    let fields = match &s.fields {
+        Fields::Named(named) => &named.named,
+        _ => {
+            return syn::Error::new_spanned(
+                &s.fields,
+                "FromJSON can only be derived for structs with named fields",
+            )
+            .to_compile_error()
+        }
+    };
+
+    let inits = fields.iter().map(|f| {
+        let ident = f.ident.as_ref().unwrap();
+        let opts = field_opts(&f.attrs);
+        let key = opts.rename.unwrap_or_else(|| ident.to_string());
+
+        let value = if opts.default {
+            quote! {
+                match o.get(#key) {
+                    Some(val) => ::fortunate_json::FromJSON::from_json(val)
+                        .map_err(|e| e.in_field(#key))?,
+                    None => ::std::default::Default::default(),
+                }
+            }
+        } else if is_option(&f.ty) {
+            quote! { ::fortunate_json::extract_optional_field(o, #key)? }
+        } else {
+            quote! { ::fortunate_json::extract_field(o, #key)? }
+        };
+
+        quote! { #ident: #value }
+    });
+
+    quote! {
+        let o = v.as_object()?;
+        Ok(Self { #(#inits),* })
+    }
+}
+
+fn from_json_enum(e: &syn::DataEnum) -> proc_macro2::TokenStream {
+    let arms = e.variants.iter().map(|variant| {
+        let ident = &variant.ident;
+        let opts = field_opts(&variant.attrs);
+        let tag = opts.rename.unwrap_or_else(|| ident.to_string());
+
+        match &variant.fields {
+            Fields::Unit => quote! { #tag => Ok(Self::#ident), },
+            Fields::Named(named) => {
+                let inits = named.named.iter().map(|f| {
+                    let fident = f.ident.as_ref().unwrap();
+                    let fopts = field_opts(&f.attrs);
+                    let key = fopts.rename.unwrap_or_else(|| fident.to_string());
+
+                    let value = if fopts.default {
+                        quote! {
+                            match inner.get(#key) {
+                                Some(val) => ::fortunate_json::FromJSON::from_json(val)
+                                    .map_err(|e| e.in_field(#key))?,
+                                None => ::std::default::Default::default(),
+                            }
+                        }
+                    } else if is_option(&f.ty) {
+                        quote! { ::fortunate_json::extract_optional_field(inner, #key)? }
+                    } else {
+                        quote! { ::fortunate_json::extract_field(inner, #key)? }
+                    };
+
+                    quote! { #fident: #value }
+                });
+                quote! {
+                    #tag => {
+                        let inner = payload.as_object()?;
+                        Ok(Self::#ident { #(#inits),* })
+                    }
+                }
+            }
+            Fields::Unnamed(unnamed) => {
+                let n = unnamed.unnamed.len();
+                if n == 1 {
+                    quote! {
+                        #tag => Ok(Self::#ident(
+                            ::fortunate_json::FromJSON::from_json(payload)
+                                .map_err(|e| e.in_field(#tag))?,
+                        )),
+                    }
+                } else {
+                    let elems = (0..n).map(|i| {
+                        quote! {
+                            ::fortunate_json::FromJSON::from_json(&items[#i])
+                                .map_err(|e| e.in_field(#tag))?
+                        }
+                    });
+                    quote! {
+                        #tag => {
+                            let items = payload.as_array().map_err(|e| e.in_field(#tag))?;
+                            if items.len() != #n {
+                                return Err(::fortunate_json::DecodeError::new().in_field(#tag));
+                            }
+                            Ok(Self::#ident(#(#elems),*))
+                        }
+                    }
+                }
+            }
+        }
+    });
+
+    // Externally tagged: a single-key object whose key names the variant and
+    // whose value carries the variant's payload.
+    quote! {
+        let o = v.as_object()?;
+        if o.len() != 1 {
+            return Err(::fortunate_json::DecodeError::new());
+        }
+        let (tag, payload) = o.iter().next().unwrap();
+        // An all-unit enum never reads the payload.
+        let _ = &payload;
+        match tag.as_str() {
+            #(#arms)*
+            _ => Err(::fortunate_json::DecodeError::new()),
+        }
+    }
+}
+
+#[proc_macro_derive(ToJSON, attributes(json))]
+pub fn derive_to_json(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+
+    let body = match &input.data {
+        Data::Struct(s) => to_json_struct(s),
+        Data::Enum(e) => to_json_enum(e),
+        Data::Union(_) => {
+            return syn::Error::new_spanned(name, "ToJSON cannot be derived for unions")
+                .to_compile_error()
+                .into()
+        }
+    };
+
+    let expanded = quote! {
+        impl ::fortunate_json::ToJSON for #name {
+            fn to_json(&self) -> ::fortunate_json::Value {
+                #body
+            }
+        }
+    };
+
+    expanded.into()
+}
+
+fn to_json_struct(s: &syn::DataStruct) -> proc_macro2::TokenStream {
+    let fields = match &s.fields {
+        Fields::Named(named) => &named.named,
+        _ => {
+            return syn::Error::new_spanned(
+                &s.fields,
+                "ToJSON can only be derived for structs with named fields",
+            )
+            .to_compile_error()
+        }
+    };
+
+    let inserts = fields.iter().map(|f| {
+        let ident = f.ident.as_ref().unwrap();
+        let opts = field_opts(&f.attrs);
+        let key = opts.rename.unwrap_or_else(|| ident.to_string());
+        quote! {
+            o.insert(#key.to_owned(), ::fortunate_json::ToJSON::to_json(&self.#ident));
+        }
+    });
+
+    quote! {
+        let mut o = ::std::collections::HashMap::new();
+        #(#inserts)*
+        ::fortunate_json::Value::Object(o)
+    }
+}
+
+fn to_json_enum(e: &syn::DataEnum) -> proc_macro2::TokenStream {
+    let arms = e.variants.iter().map(|variant| {
+        let ident = &variant.ident;
+        let opts = field_opts(&variant.attrs);
+        let tag = opts.rename.unwrap_or_else(|| ident.to_string());
+
+        match &variant.fields {
+            Fields::Unit => quote! {
+                Self::#ident => {
+                    let mut o = ::std::collections::HashMap::new();
+                    o.insert(#tag.to_owned(), ::fortunate_json::Value::Null);
+                    ::fortunate_json::Value::Object(o)
+                }
+            },
+            Fields::Named(named) => {
+                let binds = named.named.iter().map(|f| f.ident.as_ref().unwrap());
+                let inserts = named.named.iter().map(|f| {
+                    let fident = f.ident.as_ref().unwrap();
+                    let fopts = field_opts(&f.attrs);
+                    let key = fopts.rename.unwrap_or_else(|| fident.to_string());
+                    quote! {
+                        inner.insert(#key.to_owned(), ::fortunate_json::ToJSON::to_json(#fident));
+                    }
+                });
+                quote! {
+                    Self::#ident { #(#binds),* } => {
+                        let mut inner = ::std::collections::HashMap::new();
+                        #(#inserts)*
+                        let mut o = ::std::collections::HashMap::new();
+                        o.insert(#tag.to_owned(), ::fortunate_json::Value::Object(inner));
+                        ::fortunate_json::Value::Object(o)
+                    }
+                }
+            }
+            Fields::Unnamed(unnamed) => {
+                let binds: Vec<_> = (0..unnamed.unnamed.len())
+                    .map(|i| quote::format_ident!("f{}", i))
+                    .collect();
+                let payload = if binds.len() == 1 {
+                    let only = &binds[0];
+                    quote! { ::fortunate_json::ToJSON::to_json(#only) }
+                } else {
+                    let elems = binds
+                        .iter()
+                        .map(|b| quote! { ::fortunate_json::ToJSON::to_json(#b) });
+                    quote! { ::fortunate_json::Value::Array(vec![#(#elems),*]) }
+                };
+                quote! {
+                    Self::#ident(#(#binds),*) => {
+                        let mut o = ::std::collections::HashMap::new();
+                        o.insert(#tag.to_owned(), #payload);
+                        ::fortunate_json::Value::Object(o)
+                    }
+                }
+            }
+        }
+    });
+
+    quote! {
+        match self {
+            #(#arms)*
+        }
+    }
+}
@@ -1,3 +1,4 @@
+pub mod encode;
 pub mod parse;
 
 use parse::ParseError;
@@ -5,13 +6,21 @@ use std::collections::hash_map::HashMap;
 use std::hash::Hash;
 use std::str::FromStr;
 
-pub use parse::parse;
+pub use encode::{encode, pretty_encode, to_string, to_string_pretty, ToJSON};
+pub use fortunate_json_derive::{FromJSON, ToJSON};
+pub use parse::{
+    parse, parse_borrowed, parse_reader, parse_with_max_depth, ErrorCode, JsonEvent,
+    PathComponent, ReadStreamingParser, Stack, StreamingParser, ValueRef,
+};
 
 #[derive(Debug, PartialEq)]
 pub enum Value {
     Null,
     Boolean(bool),
-    Number(f32),
+    Integer(i64),
+    // Unsigned integers too large for an i64 but still exact in a u64.
+    UInt(u64),
+    Float(f64),
     String(String),
     Array(Vec<Value>),
     Object(HashMap<String, Value>),
@@ -22,15 +31,32 @@ impl Value {
         if let Value::String(s) = self {
             Ok(s)
         } else {
-            Err(DecodeError {})
+            Err(DecodeError::new())
         }
     }
 
-    pub fn as_float(&self) -> Result<f32, DecodeError> {
-        if let Value::Number(n) = self {
-            Ok(*n)
-        } else {
-            Err(DecodeError {})
+    pub fn as_float(&self) -> Result<f64, DecodeError> {
+        match self {
+            Value::Float(n) => Ok(*n),
+            Value::Integer(n) => Ok(*n as f64),
+            Value::UInt(n) => Ok(*n as f64),
+            _ => Err(DecodeError::new()),
+        }
+    }
+
+    pub fn as_integer(&self) -> Result<i64, DecodeError> {
+        match self {
+            Value::Integer(n) => Ok(*n),
+            Value::UInt(n) if *n <= i64::MAX as u64 => Ok(*n as i64),
+            _ => Err(DecodeError::new()),
+        }
+    }
+
+    pub fn as_uint(&self) -> Result<u64, DecodeError> {
+        match self {
+            Value::UInt(n) => Ok(*n),
+            Value::Integer(n) if *n >= 0 => Ok(*n as u64),
+            _ => Err(DecodeError::new()),
         }
     }
 
@@ -38,7 +64,7 @@ impl Value {
         if let Value::Array(a) = self {
             Ok(a)
         } else {
-            Err(DecodeError {})
+            Err(DecodeError::new())
         }
     }
 
@@ -46,7 +72,7 @@ impl Value {
         if let Value::Object(hm) = self {
             Ok(hm)
         } else {
-            Err(DecodeError {})
+            Err(DecodeError::new())
         }
     }
 }
@@ -56,11 +82,11 @@ where
     T: FromJSON,
 {
     let v = match o.get(key) {
-        None => return Err(DecodeError {}),
+        None => return Err(DecodeError::new().in_field(key)),
         Some(a) => a,
     };
 
-    T::from_json(v)
+    T::from_json(v).map_err(|e| e.in_field(key))
 }
 
 pub fn extract_optional_field<T>(
@@ -72,12 +98,40 @@ where
 {
     match o.get(key) {
         None => Ok(None),
-        Some(a) => Ok(Some(T::from_json(a)?)),
+        Some(a) => Ok(Some(T::from_json(a).map_err(|e| e.in_field(key))?)),
     }
 }
 
-#[derive(Debug, PartialEq)]
-pub struct DecodeError;
+// A decode failure, carrying the path of field names from the decoded root to
+// the value that could not be converted so the caller can say *where* it went
+// wrong.  The path is stored outermost-first.
+#[derive(Debug, PartialEq, Default)]
+pub struct DecodeError {
+    pub path: Vec<String>,
+}
+
+impl DecodeError {
+    pub fn new() -> DecodeError {
+        DecodeError { path: Vec::new() }
+    }
+
+    // Record that this failure happened inside `key`, extending the path toward
+    // the root as the error unwinds back up the nested decoders.
+    pub fn in_field(mut self, key: &str) -> DecodeError {
+        self.path.insert(0, key.to_owned());
+        self
+    }
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "could not decode value")
+        } else {
+            write!(f, "could not decode field `{}`", self.path.join("."))
+        }
+    }
+}
 
 pub trait FromJSON
 where
@@ -96,15 +150,44 @@ impl FromJSON for String {
 impl FromJSON for f32 {
     fn from_json(v: &Value) -> Result<Self, DecodeError> {
         let n = v.as_float()?;
-        Ok(n)
+        Ok(n as f32)
+    }
+}
+
+impl FromJSON for f64 {
+    fn from_json(v: &Value) -> Result<Self, DecodeError> {
+        v.as_float()
+    }
+}
+
+impl FromJSON for i64 {
+    fn from_json(v: &Value) -> Result<Self, DecodeError> {
+        v.as_integer()
+    }
+}
+
+impl FromJSON for u64 {
+    fn from_json(v: &Value) -> Result<Self, DecodeError> {
+        v.as_uint()
+    }
+}
+
+impl FromJSON for i32 {
+    fn from_json(v: &Value) -> Result<Self, DecodeError> {
+        let n = v.as_integer()?;
+        if n < i32::MIN as i64 || n > i32::MAX as i64 {
+            Err(DecodeError::new())
+        } else {
+            Ok(n as i32)
+        }
     }
 }
 
 impl FromJSON for u32 {
     fn from_json(v: &Value) -> Result<Self, DecodeError> {
-        let n = v.as_float()?;
-        if n != n.floor() {
-            Err(DecodeError {})
+        let n = v.as_uint()?;
+        if n > u32::MAX as u64 {
+            Err(DecodeError::new())
         } else {
             Ok(n as u32)
         }
@@ -157,7 +240,7 @@ where
             // let key = k.as_str()?;
             let key = match FromStr::from_str(k) {
                 Ok(k) => k,
-                Err(_) => return Err(DecodeError {}), // FIXME: Better error here
+                Err(_) => return Err(DecodeError::new()), // FIXME: Better error here
             };
 
             res.insert(key, FromJSON::from_json(v)?);
@@ -188,8 +271,7 @@ pub enum JSONError {
 
 impl From<ParseError> for JSONError {
     fn from(p: ParseError) -> JSONError {
-        let ParseError(msg) = p;
-        JSONError::ParseError(msg)
+        JSONError::ParseError(p.to_string())
     }
 }
 
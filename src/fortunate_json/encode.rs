@@ -0,0 +1,249 @@
+use std::collections::hash_map::HashMap;
+use std::hash::Hash;
+
+use crate::fortunate_json::Value;
+
+pub fn to_string(v: &Value) -> String {
+    let mut res = String::new();
+    write_compact(&mut res, v);
+    res
+}
+
+pub fn to_string_pretty(v: &Value, indent: usize) -> String {
+    let mut res = String::new();
+    write_pretty(&mut res, v, indent, 0);
+    res
+}
+
+// Encode any ToJSON value to compact text in one step.
+pub fn encode<T>(v: &T) -> String
+where
+    T: ToJSON,
+{
+    to_string(&v.to_json())
+}
+
+// Encode any ToJSON value to indented text in one step.
+pub fn pretty_encode<T>(v: &T, indent: usize) -> String
+where
+    T: ToJSON,
+{
+    to_string_pretty(&v.to_json(), indent)
+}
+
+fn write_compact(out: &mut String, v: &Value) {
+    match v {
+        Value::Null => out.push_str("null"),
+        Value::Boolean(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Integer(n) => out.push_str(&format!("{}", n)),
+        Value::UInt(n) => out.push_str(&format!("{}", n)),
+        Value::Float(n) => out.push_str(&format_float(*n)),
+        Value::String(s) => write_escaped(out, s),
+        Value::Array(a) => {
+            out.push('[');
+            for (i, elem) in a.iter().enumerate() {
+                if i != 0 {
+                    out.push(',');
+                }
+                write_compact(out, elem);
+            }
+            out.push(']');
+        }
+        Value::Object(hm) => {
+            out.push('{');
+            for (i, (k, val)) in hm.iter().enumerate() {
+                if i != 0 {
+                    out.push(',');
+                }
+                write_escaped(out, k);
+                out.push(':');
+                write_compact(out, val);
+            }
+            out.push('}');
+        }
+    }
+}
+
+fn write_pretty(out: &mut String, v: &Value, indent: usize, depth: usize) {
+    match v {
+        Value::Array(a) if !a.is_empty() => {
+            out.push('[');
+            for (i, elem) in a.iter().enumerate() {
+                if i != 0 {
+                    out.push(',');
+                }
+                out.push('\n');
+                push_indent(out, indent, depth + 1);
+                write_pretty(out, elem, indent, depth + 1);
+            }
+            out.push('\n');
+            push_indent(out, indent, depth);
+            out.push(']');
+        }
+        Value::Object(hm) if !hm.is_empty() => {
+            out.push('{');
+            for (i, (k, val)) in hm.iter().enumerate() {
+                if i != 0 {
+                    out.push(',');
+                }
+                out.push('\n');
+                push_indent(out, indent, depth + 1);
+                write_escaped(out, k);
+                out.push_str(": ");
+                write_pretty(out, val, indent, depth + 1);
+            }
+            out.push('\n');
+            push_indent(out, indent, depth);
+            out.push('}');
+        }
+        // Scalars and empty containers stay on one line.
+        _ => write_compact(out, v),
+    }
+}
+
+fn push_indent(out: &mut String, indent: usize, depth: usize) {
+    for _ in 0..(indent * depth) {
+        out.push(' ');
+    }
+}
+
+fn format_float(n: f64) -> String {
+    // JSON has no syntax for NaN or the infinities; emit null rather than an
+    // unparseable token.
+    if !n.is_finite() {
+        return "null".to_owned();
+    }
+    // Print whole numbers without a trailing ".0" via an i64 cast, but only
+    // below 2^53 in magnitude, where every f64 is an exact integer; larger
+    // magnitudes fall back to the default formatting.
+    if n == n.trunc() && n.abs() < 9.007199254740992e15 {
+        format!("{}", n as i64)
+    } else {
+        format!("{}", n)
+    }
+}
+
+fn write_escaped(out: &mut String, s: &str) {
+    out.push('"');
+    for ch in s.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\x08' => out.push_str("\\b"),
+            '\x0c' => out.push_str("\\f"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+pub trait ToJSON {
+    fn to_json(&self) -> Value;
+}
+
+impl ToJSON for String {
+    fn to_json(&self) -> Value {
+        Value::String(self.clone())
+    }
+}
+
+impl ToJSON for str {
+    fn to_json(&self) -> Value {
+        Value::String(self.to_owned())
+    }
+}
+
+impl ToJSON for bool {
+    fn to_json(&self) -> Value {
+        Value::Boolean(*self)
+    }
+}
+
+impl ToJSON for f32 {
+    fn to_json(&self) -> Value {
+        Value::Float(*self as f64)
+    }
+}
+
+impl ToJSON for f64 {
+    fn to_json(&self) -> Value {
+        Value::Float(*self)
+    }
+}
+
+impl ToJSON for i64 {
+    fn to_json(&self) -> Value {
+        Value::Integer(*self)
+    }
+}
+
+impl ToJSON for i32 {
+    fn to_json(&self) -> Value {
+        Value::Integer(*self as i64)
+    }
+}
+
+impl ToJSON for u32 {
+    fn to_json(&self) -> Value {
+        Value::Integer(*self as i64)
+    }
+}
+
+impl ToJSON for u64 {
+    fn to_json(&self) -> Value {
+        // Values that still fit in an i64 stay an Integer so round-trips are stable.
+        if *self <= i64::MAX as u64 {
+            Value::Integer(*self as i64)
+        } else {
+            Value::UInt(*self)
+        }
+    }
+}
+
+impl<T> ToJSON for Vec<T>
+where
+    T: ToJSON,
+{
+    fn to_json(&self) -> Value {
+        Value::Array(self.iter().map(ToJSON::to_json).collect())
+    }
+}
+
+impl<T> ToJSON for std::collections::HashSet<T>
+where
+    T: ToJSON + Eq + Hash,
+{
+    fn to_json(&self) -> Value {
+        Value::Array(self.iter().map(ToJSON::to_json).collect())
+    }
+}
+
+impl<K, V> ToJSON for HashMap<K, V>
+where
+    K: ToString + Eq + Hash,
+    V: ToJSON,
+{
+    fn to_json(&self) -> Value {
+        Value::Object(
+            self.iter()
+                .map(|(k, v)| (k.to_string(), v.to_json()))
+                .collect(),
+        )
+    }
+}
+
+impl<T> ToJSON for Option<T>
+where
+    T: ToJSON,
+{
+    fn to_json(&self) -> Value {
+        match self {
+            None => Value::Null,
+            Some(v) => v.to_json(),
+        }
+    }
+}
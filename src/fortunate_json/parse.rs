@@ -1,9 +1,83 @@
+use std::borrow::Cow;
 use std::collections::hash_map::HashMap;
+use std::io::Read;
 
 use crate::fortunate_json::Value;
 
+// A machine-readable description of what went wrong, modeled on the ErrorCode
+// enum in rust's old libserialize::json.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ErrorCode {
+    UnexpectedEndOfFile,
+    UnexpectedCharacter,
+    InvalidNumber,
+    InvalidEscape,
+    InvalidUnicodeEscape,
+    KeyMustBeAString,
+    ExpectedColon,
+    ExpectedListCommaOrEnd,
+    ExpectedObjectCommaOrEnd,
+    ExpectedValue,
+    TrailingCharacters,
+    DepthLimitExceeded,
+    IoError,
+    InvalidUtf8,
+}
+
+impl ErrorCode {
+    fn message(self) -> &'static str {
+        match self {
+            ErrorCode::UnexpectedEndOfFile => "unexpected end of file",
+            ErrorCode::UnexpectedCharacter => "unexpected character",
+            ErrorCode::InvalidNumber => "invalid number",
+            ErrorCode::InvalidEscape => "invalid escape sequence",
+            ErrorCode::InvalidUnicodeEscape => "invalid unicode escape",
+            ErrorCode::KeyMustBeAString => "object key must be a string",
+            ErrorCode::ExpectedColon => "expected ':'",
+            ErrorCode::ExpectedListCommaOrEnd => "expected ',' or ']'",
+            ErrorCode::ExpectedObjectCommaOrEnd => "expected ',' or '}'",
+            ErrorCode::ExpectedValue => "expected a value",
+            ErrorCode::TrailingCharacters => "trailing characters after document",
+            ErrorCode::DepthLimitExceeded => "maximum nesting depth exceeded",
+            ErrorCode::IoError => "i/o error while reading input",
+            ErrorCode::InvalidUtf8 => "invalid utf-8 in string literal",
+        }
+    }
+}
+
+// A parse failure tagged with both a machine-readable code and the byte offset
+// and line/column where it occurred.
 #[derive(Debug, PartialEq)]
-pub struct ParseError(pub String);
+pub struct ParseError {
+    pub code: ErrorCode,
+    pub offset: usize,
+    pub line: usize,
+    pub col: usize,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.col, self.code.message())
+    }
+}
+
+// Build an error positioned at a previously-captured span.
+fn error_at(code: ErrorCode, span: Span) -> ParseError {
+    ParseError {
+        code,
+        offset: span.start,
+        line: span.line,
+        col: span.col,
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub col: usize,
+}
 
 #[derive(Debug, PartialEq)]
 enum Token<'a> {
@@ -14,18 +88,28 @@ enum Token<'a> {
     Colon,
     Comma,
     Identifier(&'a [u8]),
-    String(String),
-    Number(f32),
+    // Borrows directly from the input when the literal has no escapes.
+    String(Cow<'a, str>),
+    Integer(i64),
+    UInt(u64),
+    Float(f64),
 }
 
 struct Lexer<'a> {
     s: &'a [u8],
     pos: usize,
+    line: usize,
+    col: usize,
 }
 
 impl<'a> Lexer<'a> {
     fn new(s: &[u8]) -> Lexer<'_> {
-        Lexer { s: s, pos: 0 }
+        Lexer {
+            s: s,
+            pos: 0,
+            line: 1,
+            col: 1,
+        }
     }
 
     fn eof(&self) -> bool {
@@ -34,10 +118,26 @@ impl<'a> Lexer<'a> {
 
     fn advance(&mut self) {
         if !self.eof() {
+            if self.s[self.pos] == b'\n' {
+                self.line += 1;
+                self.col = 1;
+            } else {
+                self.col += 1;
+            }
             self.pos += 1;
         }
     }
 
+    // Tag an error code with the lexer's current byte offset and line/column.
+    fn error(&self, code: ErrorCode) -> ParseError {
+        ParseError {
+            code,
+            offset: self.pos,
+            line: self.line,
+            col: self.col,
+        }
+    }
+
     fn peek_byte(&self) -> Option<u8> {
         if self.eof() {
             None
@@ -67,7 +167,7 @@ impl<'a> Lexer<'a> {
     }
 
     fn is_identifier_start(b: u8) -> bool {
-        (b >= b'a' && b <= b'z') || (b >= b'A' && b <= b'Z') || b == b'_'
+        b.is_ascii_alphabetic() || b == b'_'
     }
 
     fn is_identifier_char(b: u8) -> bool {
@@ -75,16 +175,20 @@ impl<'a> Lexer<'a> {
     }
 
     fn is_digit(b: u8) -> bool {
-        b >= b'0' && b <= b'9'
+        b.is_ascii_digit()
     }
 
-    fn token(&mut self) -> Result<Token<'a>, ParseError> {
+    fn token(&mut self) -> Result<(Token<'a>, Span), ParseError> {
         self.skip_whitespace();
 
         if self.eof() {
-            return Err(ParseError("Unexpected end of file".to_owned()));
+            return Err(self.error(ErrorCode::UnexpectedEndOfFile));
         }
 
+        let start = self.pos;
+        let line = self.line;
+        let col = self.col;
+
         let byte = self.peek_byte().unwrap();
 
         let result = match byte as char {
@@ -112,8 +216,8 @@ impl<'a> Lexer<'a> {
                 self.advance();
                 Token::CloseBrace
             }
-            '-' => Token::Number(self.lex_number()?),
-            d if d.is_digit(10) => Token::Number(self.lex_number()?),
+            '-' => self.lex_number()?,
+            d if d.is_digit(10) => self.lex_number()?,
 
             '"' => {
                 // First, just find the extent of the string literal
@@ -121,22 +225,18 @@ impl<'a> Lexer<'a> {
                 let start_pos = self.pos;
                 loop {
                     match self.peek_byte() {
-                        None => {
-                            return Err(ParseError(
-                                "Unexpected end of file while parsing string literal".to_owned(),
-                            ))
-                        }
+                        None => return Err(self.error(ErrorCode::UnexpectedEndOfFile)),
                         Some(b) => match b as char {
-                            '\n' => {
-                                return Err(ParseError(
-                                    "Unexpected newline while parsing string literal".to_owned(),
-                                ))
-                            }
+                            '\n' => return Err(self.error(ErrorCode::UnexpectedCharacter)),
                             '\\' => {
+                                // Consume both the backslash and the byte it
+                                // escapes so that an escaped quote or backslash
+                                // is not mistaken for the string terminator.
                                 self.advance();
                                 if let None = self.peek_byte() {
-                                    return Err(ParseError("Unexpected end of file while parsing string literal escape sequence".to_owned()));
+                                    return Err(self.error(ErrorCode::UnexpectedEndOfFile));
                                 }
+                                self.advance();
                             }
                             '"' => break,
                             _ => self.advance(),
@@ -148,43 +248,62 @@ impl<'a> Lexer<'a> {
                 let res = &self.s[start_pos..end_pos];
 
                 self.advance();
-                Token::String(Self::parse_string(res)?)
+                // The input is raw bytes, so check the literal is UTF-8 before
+                // decoding or borrowing it.
+                let text = match std::str::from_utf8(res) {
+                    Ok(text) => text,
+                    Err(_) => return Err(self.error(ErrorCode::InvalidUtf8)),
+                };
+                // Only allocate when the literal actually contains an escape;
+                // otherwise borrow straight out of the input.
+                if res.contains(&b'\\') {
+                    Token::String(Cow::Owned(self.parse_string(res)?))
+                } else {
+                    Token::String(Cow::Borrowed(text))
+                }
             }
             _ if Self::is_identifier_start(byte) => {
                 Token::Identifier(self.take_while(Self::is_identifier_char))
             }
             _ => {
-                return Err(ParseError(format!(
-                    "Unexpected character '{}'",
-                    byte as char
-                )));
+                return Err(self.error(ErrorCode::UnexpectedCharacter));
             }
         };
 
+        let span = Span {
+            start,
+            end: self.pos,
+            line,
+            col,
+        };
+
         self.skip_whitespace();
 
-        Ok(result)
+        Ok((result, span))
     }
 
-    fn lex_number(&mut self) -> Result<f32, ParseError> {
-        let negative = if self.peek_byte() == Some(b'-') {
+    fn lex_number(&mut self) -> Result<Token<'a>, ParseError> {
+        // The leading '-' is part of the lexeme so that integer and float
+        // parsing both see the whole slice.
+        let start_offset = self.pos;
+
+        if self.peek_byte() == Some(b'-') {
             self.advance();
-            true
-        } else {
-            false
-        };
+        }
 
         if self.eof() {
-            return Err(ParseError("Unexpected EOF while parsing number".to_owned()));
+            return Err(self.error(ErrorCode::InvalidNumber));
         }
 
-        let start_offset = self.pos;
-
         // TODO: leading zeroes are not ok.  Only one leading zero before a decimal is allowed.
 
         self.take_while(&Self::is_digit);
 
+        // An integer literal has no fractional or exponent part.
+        let mut is_float = false;
+
         if self.peek_byte() == Some(b'.') {
+            is_float = true;
             self.advance();
 
             self.take_while(&Self::is_digit);
@@ -192,6 +311,7 @@ impl<'a> Lexer<'a> {
 
         if let Some(ch) = self.peek_byte() {
             if ch == b'e' || ch == b'E' {
+                is_float = true;
                 self.advance();
 
                 let maybe_sign = self.peek_byte();
@@ -203,173 +323,1033 @@ impl<'a> Lexer<'a> {
             }
         }
 
-        let end_effset = self.pos;
-
-        let res = std::str::from_utf8(&self.s[start_offset..end_effset])
-            .unwrap()
-            .parse::<f32>()
-            .unwrap();
+        let end_offset = self.pos;
 
-        Ok(if negative { -(res as f32) } else { res as f32 })
-    }
+        let lexeme = std::str::from_utf8(&self.s[start_offset..end_offset]).unwrap();
 
-    fn parse_hex_digit(d: char) -> Result<usize, ParseError> {
-        const DIGITS: &str = "01234567890ABCDEF";
-        if let Some(i) = DIGITS.find(d.to_ascii_uppercase()) {
-            Ok(i)
+        if is_float {
+            match lexeme.parse::<f64>() {
+                Ok(f) => Ok(Token::Float(f)),
+                Err(_) => Err(self.error(ErrorCode::InvalidNumber)),
+            }
         } else {
-            Err(ParseError(format!(
-                "Bad hex digit '{}' in unicode escape",
-                d
-            )))
-        }
-    }
-
-    fn parse_hex(d1: char, d2: char, d3: char, d4: char) -> Result<u32, ParseError> {
-        let a1 = Self::parse_hex_digit(d1)?;
-        let a2 = Self::parse_hex_digit(d2)?;
-        let a3 = Self::parse_hex_digit(d3)?;
-        let a4 = Self::parse_hex_digit(d4)?;
-        Ok((a1 << 24 | a2 << 16 | a3 << 8 | a4) as u32)
-    }
-
-    fn parse_string(s: &[u8]) -> Result<String, ParseError> {
-        let mut res = String::new();
-        res.reserve_exact(s.len());
-
-        let st = std::str::from_utf8(s).unwrap();
-
-        let mut chars = st.chars();
-
-        while let Some(ch) = chars.next() {
-            if ch == '\\' {
-                let n = chars.next().unwrap(); // Should be ok.  Lexer should handle this.
-                res.push(match n {
-                    '"' => '"',
-                    '\\' => '\\',
-                    '/' => '/',
-                    'b' => '\x08',
-                    'f' => '\x0c',
-                    'n' => '\n',
-                    'r' => '\r',
-                    't' => '\t',
-                    'u' => {
-                        let gch = |ch: &mut std::str::Chars| match ch.next() {
-                            None => Err(ParseError(
-                                "Unexpected EOF when parsing unicode escape in string literal"
-                                    .to_owned(),
-                            )),
-                            Some(c) => Ok(c),
-                        };
-                        let d1 = gch(&mut chars)?;
-                        let d2 = gch(&mut chars)?;
-                        let d3 = gch(&mut chars)?;
-                        let d4 = gch(&mut chars)?;
-                        char::from_u32(Self::parse_hex(d1, d2, d3, d4)?).unwrap()
-                    }
-                    c => c,
-                });
+            // Prefer an exact integer: i64, then u64 for large positive ids,
+            // and only fall back to a float when the value overflows both.
+            if let Ok(i) = lexeme.parse::<i64>() {
+                Ok(Token::Integer(i))
+            } else if let Ok(u) = lexeme.parse::<u64>() {
+                Ok(Token::UInt(u))
             } else {
-                res.push(ch);
+                match lexeme.parse::<f64>() {
+                    Ok(f) => Ok(Token::Float(f)),
+                    Err(_) => Err(self.error(ErrorCode::InvalidNumber)),
+                }
             }
         }
+    }
 
-        Ok(res)
+    fn parse_string(&self, s: &[u8]) -> Result<String, ParseError> {
+        decode_escapes(s).map_err(|code| self.error(code))
     }
+}
 
-    fn rest(&self) -> &'a [u8] {
-        &self.s[self.pos..self.s.len()]
+fn parse_hex_digit(d: char) -> Result<u32, ErrorCode> {
+    match d {
+        '0'..='9' => Ok(d as u32 - '0' as u32),
+        'a'..='f' => Ok(d as u32 - 'a' as u32 + 10),
+        'A'..='F' => Ok(d as u32 - 'A' as u32 + 10),
+        _ => Err(ErrorCode::InvalidEscape),
     }
 }
 
-pub fn parse(s: &str) -> Result<Value, ParseError> {
-    let mut lexer = Lexer::new(s.as_bytes());
-    let v = parse_(&mut lexer)?;
+fn parse_hex(d1: char, d2: char, d3: char, d4: char) -> Result<u32, ErrorCode> {
+    let h0 = parse_hex_digit(d1)?;
+    let h1 = parse_hex_digit(d2)?;
+    let h2 = parse_hex_digit(d3)?;
+    let h3 = parse_hex_digit(d4)?;
+    Ok((h0 << 12) | (h1 << 8) | (h2 << 4) | h3)
+}
 
-    lexer.skip_whitespace();
+// Read the four hex digits of a \u escape and combine them into a UTF-16
+// code unit.
+fn parse_unicode_escape(chars: &mut std::str::Chars) -> Result<u32, ErrorCode> {
+    let d1 = chars.next().ok_or(ErrorCode::UnexpectedEndOfFile)?;
+    let d2 = chars.next().ok_or(ErrorCode::UnexpectedEndOfFile)?;
+    let d3 = chars.next().ok_or(ErrorCode::UnexpectedEndOfFile)?;
+    let d4 = chars.next().ok_or(ErrorCode::UnexpectedEndOfFile)?;
+    parse_hex(d1, d2, d3, d4)
+}
+
+// Decode the backslash escapes in the body of a string literal.  The lexer has
+// already verified that the slice is valid UTF-8 and that every backslash is
+// followed by at least one more character.
+fn decode_escapes(s: &[u8]) -> Result<String, ErrorCode> {
+    let mut res = String::new();
+    res.reserve_exact(s.len());
+
+    let st = std::str::from_utf8(s).unwrap();
 
-    if !lexer.eof() {
-        Err(ParseError(format!(
-            "Extra goop at the end of the file: {:?}",
-            lexer.rest()
-        )))
-    } else {
-        Ok(v)
+    let mut chars = st.chars();
+
+    while let Some(ch) = chars.next() {
+        if ch == '\\' {
+            let n = chars.next().ok_or(ErrorCode::InvalidEscape)?;
+            res.push(match n {
+                '"' => '"',
+                '\\' => '\\',
+                '/' => '/',
+                'b' => '\x08',
+                'f' => '\x0c',
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                'u' => {
+                    let unit = parse_unicode_escape(&mut chars)?;
+                    match unit {
+                        // A high surrogate must be followed by a \u low surrogate.
+                        0xD800..=0xDBFF => {
+                            if chars.next() != Some('\\') || chars.next() != Some('u') {
+                                return Err(ErrorCode::InvalidUnicodeEscape);
+                            }
+                            let low = parse_unicode_escape(&mut chars)?;
+                            if !(0xDC00..=0xDFFF).contains(&low) {
+                                return Err(ErrorCode::InvalidUnicodeEscape);
+                            }
+                            let scalar = 0x10000 + ((unit - 0xD800) << 10) + (low - 0xDC00);
+                            match char::from_u32(scalar) {
+                                Some(c) => c,
+                                None => return Err(ErrorCode::InvalidUnicodeEscape),
+                            }
+                        }
+                        // A low surrogate with no preceding high surrogate is invalid.
+                        0xDC00..=0xDFFF => return Err(ErrorCode::InvalidUnicodeEscape),
+                        _ => match char::from_u32(unit) {
+                            Some(c) => c,
+                            None => return Err(ErrorCode::InvalidUnicodeEscape),
+                        },
+                    }
+                }
+                c => c,
+            });
+        } else {
+            res.push(ch);
+        }
     }
+
+    Ok(res)
 }
 
 const NULL_TOKEN: &'static [u8] = b"null";
 const TRUE_TOKEN: &'static [u8] = b"true";
 const FALSE_TOKEN: &'static [u8] = b"false";
 
-fn parse_(lexer: &mut Lexer) -> Result<Value, ParseError> {
-    let token = lexer.token()?;
-    dbg!("token '{:?}'", &token);
-
-    match token {
-        Token::Identifier(i) if i == NULL_TOKEN => Ok(Value::Null),
-        Token::Identifier(i) if i == TRUE_TOKEN => Ok(Value::Boolean(true)),
-        Token::Identifier(i) if i == FALSE_TOKEN => Ok(Value::Boolean(false)),
-        Token::String(s) => Ok(Value::String(s)),
-        Token::Number(n) => Ok(Value::Number(n)),
-        Token::OpenBracket => {
-            let mut arr = Vec::new();
-            loop {
-                let val = parse_(lexer)?;
-                arr.push(val);
-
-                let next = lexer.token()?;
-                match next {
-                    Token::CloseBracket => break,
-                    Token::Comma => continue,
-                    _ => {
-                        return Err(ParseError(format!(
-                            "Expected ',' or ']' but got '{:?}'",
-                            next
-                        )));
-                    }
-                }
+// The events emitted by the pull parser, modeled on libserialize's JsonEvent.
+// String payloads borrow from the input when they have no escapes.
+#[derive(Debug, PartialEq)]
+pub enum JsonEvent<'a> {
+    NullValue,
+    BooleanValue(bool),
+    IntegerValue(i64),
+    UIntValue(u64),
+    FloatValue(f64),
+    StringValue(Cow<'a, str>),
+    ArrayStart,
+    ArrayEnd,
+    ObjectStart,
+    ObjectEnd,
+    ObjectKey(Cow<'a, str>),
+}
+
+// A borrowed value tree.  Strings and object keys point straight at the input
+// whenever they contain no escape sequences, allocating only when they must.
+#[derive(Debug, PartialEq)]
+pub enum ValueRef<'a> {
+    Null,
+    Boolean(bool),
+    Integer(i64),
+    UInt(u64),
+    Float(f64),
+    String(Cow<'a, str>),
+    Array(Vec<ValueRef<'a>>),
+    Object(HashMap<Cow<'a, str>, ValueRef<'a>>),
+}
+
+impl<'a> ValueRef<'a> {
+    // Copy the borrowed tree into a fully owned Value.
+    pub fn into_owned(self) -> Value {
+        match self {
+            ValueRef::Null => Value::Null,
+            ValueRef::Boolean(b) => Value::Boolean(b),
+            ValueRef::Integer(n) => Value::Integer(n),
+            ValueRef::UInt(n) => Value::UInt(n),
+            ValueRef::Float(n) => Value::Float(n),
+            ValueRef::String(s) => Value::String(s.into_owned()),
+            ValueRef::Array(a) => Value::Array(a.into_iter().map(ValueRef::into_owned).collect()),
+            ValueRef::Object(o) => Value::Object(
+                o.into_iter()
+                    .map(|(k, v)| (k.into_owned(), v.into_owned()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+// A single component of the path from the document root to the cursor.
+#[derive(Debug, PartialEq, Clone)]
+pub enum PathComponent {
+    Index(usize),
+    Key(String),
+}
+
+// What the parser is expecting to read next inside each open container.  The
+// public event stream only distinguishes array/object, but separator handling
+// needs a few more internal states.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Frame {
+    ArrayStart,  // just after '[': a value or ']'
+    ArrayValue,  // just after ',': a value
+    ArrayComma,  // just after a value: ',' or ']'
+    ObjectStart, // just after '{': a key or '}'
+    ObjectKey,   // just after ',': a key
+    ObjectColon, // just after a key: ':'
+    ObjectValue, // just after ':': a value
+    ObjectComma, // just after a value: ',' or '}'
+}
+
+// The container stack, carrying both the parser states and the root-to-cursor
+// path so a caller can filter events by location.
+pub struct Stack {
+    frames: Vec<Frame>,
+    path: Vec<PathComponent>,
+}
+
+impl Stack {
+    fn new() -> Stack {
+        Stack {
+            frames: Vec::new(),
+            path: Vec::new(),
+        }
+    }
+
+    fn top(&self) -> Option<Frame> {
+        self.frames.last().copied()
+    }
+
+    fn set_top(&mut self, f: Frame) {
+        if let Some(t) = self.frames.last_mut() {
+            *t = f;
+        }
+    }
+
+    fn push_array(&mut self) {
+        self.frames.push(Frame::ArrayStart);
+        self.path.push(PathComponent::Index(0));
+    }
+
+    fn push_object(&mut self) {
+        self.frames.push(Frame::ObjectStart);
+        self.path.push(PathComponent::Key(String::new()));
+    }
+
+    fn pop(&mut self) {
+        self.frames.pop();
+        self.path.pop();
+    }
+
+    fn next_index(&mut self) {
+        if let Some(PathComponent::Index(i)) = self.path.last_mut() {
+            *i += 1;
+        }
+    }
+
+    fn set_key(&mut self, k: String) {
+        if let Some(c) = self.path.last_mut() {
+            *c = PathComponent::Key(k);
+        }
+    }
+
+    pub fn path(&self) -> &[PathComponent] {
+        &self.path
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    // The number of currently-open containers.
+    fn depth(&self) -> usize {
+        self.frames.len()
+    }
+}
+
+enum Step<'a> {
+    Emit(JsonEvent<'a>),
+    Continue,
+    Error(ParseError),
+}
+
+// A source of tokens driving the pull parser.  The slice `Lexer` and the
+// reader-backed `ReaderLexer` both implement it, so the state machine below is
+// written once and shared.
+trait TokenStream<'a> {
+    fn next_token(&mut self) -> Result<(Token<'a>, Span), ParseError>;
+    // Called once the root value is complete: consume trailing whitespace and
+    // fail if any other characters remain.
+    fn finish(&mut self) -> Result<(), ParseError>;
+}
+
+impl<'a> TokenStream<'a> for Lexer<'a> {
+    fn next_token(&mut self) -> Result<(Token<'a>, Span), ParseError> {
+        self.token()
+    }
+
+    fn finish(&mut self) -> Result<(), ParseError> {
+        self.skip_whitespace();
+        if self.eof() {
+            Ok(())
+        } else {
+            Err(self.error(ErrorCode::TrailingCharacters))
+        }
+    }
+}
+
+// The token-driven state machine, generic over where the tokens come from.  It
+// holds only the current nesting path, never the whole document.
+struct Machine<'a, S: TokenStream<'a>> {
+    source: S,
+    stack: Stack,
+    max_depth: Option<usize>,
+    root_done: bool,
+    finished: bool,
+    _marker: std::marker::PhantomData<&'a ()>,
+}
+
+impl<'a, S: TokenStream<'a>> Machine<'a, S> {
+    fn new(source: S, max_depth: Option<usize>) -> Machine<'a, S> {
+        Machine {
+            source,
+            stack: Stack::new(),
+            max_depth,
+            root_done: false,
+            finished: false,
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    // True when opening another container would pass the configured limit.
+    fn depth_exceeded(&self) -> bool {
+        matches!(self.max_depth, Some(max) if self.stack.depth() >= max)
+    }
+
+    // Mark the value that just finished as complete, advancing the parent.
+    fn complete_value(&mut self) {
+        match self.stack.top() {
+            None => self.root_done = true,
+            Some(Frame::ArrayStart) | Some(Frame::ArrayValue) => {
+                self.stack.set_top(Frame::ArrayComma)
             }
+            Some(Frame::ObjectValue) => self.stack.set_top(Frame::ObjectComma),
+            _ => {}
+        }
+    }
 
-            Ok(Value::Array(arr))
+    fn value_event(&mut self, token: Token<'a>, span: Span) -> Step<'a> {
+        match token {
+            Token::Identifier(i) if i == NULL_TOKEN => {
+                self.complete_value();
+                Step::Emit(JsonEvent::NullValue)
+            }
+            Token::Identifier(i) if i == TRUE_TOKEN => {
+                self.complete_value();
+                Step::Emit(JsonEvent::BooleanValue(true))
+            }
+            Token::Identifier(i) if i == FALSE_TOKEN => {
+                self.complete_value();
+                Step::Emit(JsonEvent::BooleanValue(false))
+            }
+            Token::Integer(n) => {
+                self.complete_value();
+                Step::Emit(JsonEvent::IntegerValue(n))
+            }
+            Token::UInt(n) => {
+                self.complete_value();
+                Step::Emit(JsonEvent::UIntValue(n))
+            }
+            Token::Float(n) => {
+                self.complete_value();
+                Step::Emit(JsonEvent::FloatValue(n))
+            }
+            Token::String(s) => {
+                self.complete_value();
+                Step::Emit(JsonEvent::StringValue(s))
+            }
+            Token::OpenBracket => {
+                if self.depth_exceeded() {
+                    return Step::Error(error_at(ErrorCode::DepthLimitExceeded, span));
+                }
+                self.stack.push_array();
+                Step::Emit(JsonEvent::ArrayStart)
+            }
+            Token::OpenBrace => {
+                if self.depth_exceeded() {
+                    return Step::Error(error_at(ErrorCode::DepthLimitExceeded, span));
+                }
+                self.stack.push_object();
+                Step::Emit(JsonEvent::ObjectStart)
+            }
+            _ => Step::Error(error_at(ErrorCode::ExpectedValue, span)),
         }
-        Token::OpenBrace => {
-            let mut obj = HashMap::new();
+    }
 
-            loop {
-                let key = match parse_(lexer)? {
-                    Value::String(s) => s,
-                    other => {
-                        return Err(ParseError(format!(
-                            "Object keys must be strings.  Got {:?}",
-                            other
-                        )))
+    fn step(&mut self) -> Step<'a> {
+        match self.stack.top() {
+            None => {
+                let (token, span) = match self.source.next_token() {
+                    Ok(t) => t,
+                    Err(e) => return Step::Error(e),
+                };
+                self.value_event(token, span)
+            }
+            Some(Frame::ArrayStart) | Some(Frame::ArrayValue) => {
+                let allow_end = self.stack.top() == Some(Frame::ArrayStart);
+                let (token, span) = match self.source.next_token() {
+                    Ok(t) => t,
+                    Err(e) => return Step::Error(e),
+                };
+                if token == Token::CloseBracket {
+                    if !allow_end {
+                        return Step::Error(error_at(ErrorCode::ExpectedValue, span));
                     }
+                    self.stack.pop();
+                    self.complete_value();
+                    Step::Emit(JsonEvent::ArrayEnd)
+                } else {
+                    self.value_event(token, span)
+                }
+            }
+            Some(Frame::ArrayComma) => {
+                let (token, span) = match self.source.next_token() {
+                    Ok(t) => t,
+                    Err(e) => return Step::Error(e),
                 };
+                match token {
+                    Token::CloseBracket => {
+                        self.stack.pop();
+                        self.complete_value();
+                        Step::Emit(JsonEvent::ArrayEnd)
+                    }
+                    Token::Comma => {
+                        self.stack.next_index();
+                        self.stack.set_top(Frame::ArrayValue);
+                        Step::Continue
+                    }
+                    _ => Step::Error(error_at(ErrorCode::ExpectedListCommaOrEnd, span)),
+                }
+            }
+            Some(Frame::ObjectStart) | Some(Frame::ObjectKey) => {
+                let allow_end = self.stack.top() == Some(Frame::ObjectStart);
+                let (token, span) = match self.source.next_token() {
+                    Ok(t) => t,
+                    Err(e) => return Step::Error(e),
+                };
+                match token {
+                    Token::CloseBrace if allow_end => {
+                        self.stack.pop();
+                        self.complete_value();
+                        Step::Emit(JsonEvent::ObjectEnd)
+                    }
+                    Token::String(s) => {
+                        self.stack.set_key(s.to_string());
+                        self.stack.set_top(Frame::ObjectColon);
+                        Step::Emit(JsonEvent::ObjectKey(s))
+                    }
+                    _ => Step::Error(error_at(ErrorCode::KeyMustBeAString, span)),
+                }
+            }
+            Some(Frame::ObjectColon) => {
+                let (token, span) = match self.source.next_token() {
+                    Ok(t) => t,
+                    Err(e) => return Step::Error(e),
+                };
+                if token == Token::Colon {
+                    self.stack.set_top(Frame::ObjectValue);
+                    Step::Continue
+                } else {
+                    Step::Error(error_at(ErrorCode::ExpectedColon, span))
+                }
+            }
+            Some(Frame::ObjectValue) => {
+                let (token, span) = match self.source.next_token() {
+                    Ok(t) => t,
+                    Err(e) => return Step::Error(e),
+                };
+                self.value_event(token, span)
+            }
+            Some(Frame::ObjectComma) => {
+                let (token, span) = match self.source.next_token() {
+                    Ok(t) => t,
+                    Err(e) => return Step::Error(e),
+                };
+                match token {
+                    Token::CloseBrace => {
+                        self.stack.pop();
+                        self.complete_value();
+                        Step::Emit(JsonEvent::ObjectEnd)
+                    }
+                    Token::Comma => {
+                        self.stack.set_top(Frame::ObjectKey);
+                        Step::Continue
+                    }
+                    _ => Step::Error(error_at(ErrorCode::ExpectedObjectCommaOrEnd, span)),
+                }
+            }
+        }
+    }
+
+    fn next_event(&mut self) -> Option<Result<JsonEvent<'a>, ParseError>> {
+        if self.finished {
+            return None;
+        }
+
+        // The whole document has been read; only trailing goop remains to be
+        // checked for.
+        if self.root_done {
+            self.finished = true;
+            return match self.source.finish() {
+                Ok(()) => None,
+                Err(e) => Some(Err(e)),
+            };
+        }
+
+        loop {
+            match self.step() {
+                Step::Emit(ev) => return Some(Ok(ev)),
+                Step::Continue => continue,
+                Step::Error(e) => {
+                    self.finished = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+// A pull parser that turns a string into a flat sequence of JsonEvents driven
+// by an explicit Stack rather than recursion, so deeply nested input cannot
+// overflow the call stack.
+pub struct StreamingParser<'a> {
+    machine: Machine<'a, Lexer<'a>>,
+}
+
+impl<'a> StreamingParser<'a> {
+    pub fn new(s: &'a [u8]) -> StreamingParser<'a> {
+        StreamingParser {
+            machine: Machine::new(Lexer::new(s), None),
+        }
+    }
 
-                let colon = lexer.token()?;
-                if Token::Colon != colon {
-                    return Err(ParseError(format!("Expected colon but got '{:?}'", colon)));
+    // Like `new`, but rejects input nested deeper than `max_depth` containers
+    // with a clean error instead of spending unbounded heap on untrusted data.
+    pub fn with_max_depth(s: &'a [u8], max_depth: usize) -> StreamingParser<'a> {
+        StreamingParser {
+            machine: Machine::new(Lexer::new(s), Some(max_depth)),
+        }
+    }
+
+    // The path from the document root to the cursor, valid between events.
+    pub fn stack(&self) -> &Stack {
+        &self.machine.stack
+    }
+}
+
+impl<'a> Iterator for StreamingParser<'a> {
+    type Item = Result<JsonEvent<'a>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.machine.next_event()
+    }
+}
+
+// A partially-built container while folding events back into a Value.
+enum Partial {
+    Array(Vec<Value>),
+    Object(HashMap<String, Value>, Option<String>),
+}
+
+fn place(stack: &mut [Partial], result: &mut Option<Value>, value: Value) {
+    match stack.last_mut() {
+        None => *result = Some(value),
+        Some(Partial::Array(a)) => a.push(value),
+        Some(Partial::Object(o, key)) => {
+            if let Some(k) = key.take() {
+                o.insert(k, value);
+            }
+        }
+    }
+}
+
+pub fn parse(s: &str) -> Result<Value, ParseError> {
+    parse_events(StreamingParser::new(s.as_bytes()))
+}
+
+// Parse while rejecting input nested deeper than `max_depth` containers, so a
+// pathological `[[[[...]]]]` document fails cleanly instead of exhausting heap.
+pub fn parse_with_max_depth(s: &str, max_depth: usize) -> Result<Value, ParseError> {
+    parse_events(StreamingParser::with_max_depth(s.as_bytes(), max_depth))
+}
+
+// Fold any JsonEvent stream back into an owned Value tree.  Shared by the
+// string and reader entry points.
+fn parse_events<'a, I>(events: I) -> Result<Value, ParseError>
+where
+    I: IntoIterator<Item = Result<JsonEvent<'a>, ParseError>>,
+{
+    let mut stack: Vec<Partial> = Vec::new();
+    let mut result: Option<Value> = None;
+
+    for ev in events {
+        match ev? {
+            JsonEvent::NullValue => place(&mut stack, &mut result, Value::Null),
+            JsonEvent::BooleanValue(b) => place(&mut stack, &mut result, Value::Boolean(b)),
+            JsonEvent::IntegerValue(n) => place(&mut stack, &mut result, Value::Integer(n)),
+            JsonEvent::UIntValue(n) => place(&mut stack, &mut result, Value::UInt(n)),
+            JsonEvent::FloatValue(n) => place(&mut stack, &mut result, Value::Float(n)),
+            JsonEvent::StringValue(s) => {
+                place(&mut stack, &mut result, Value::String(s.into_owned()))
+            }
+            JsonEvent::ArrayStart => stack.push(Partial::Array(Vec::new())),
+            JsonEvent::ObjectStart => stack.push(Partial::Object(HashMap::new(), None)),
+            JsonEvent::ObjectKey(k) => {
+                if let Some(Partial::Object(_, key)) = stack.last_mut() {
+                    *key = Some(k.into_owned());
+                }
+            }
+            JsonEvent::ArrayEnd => {
+                if let Some(Partial::Array(a)) = stack.pop() {
+                    place(&mut stack, &mut result, Value::Array(a));
                 }
+            }
+            JsonEvent::ObjectEnd => {
+                if let Some(Partial::Object(o, _)) = stack.pop() {
+                    place(&mut stack, &mut result, Value::Object(o));
+                }
+            }
+        }
+    }
 
-                let val = parse_(lexer)?;
+    // An empty stream surfaces as an end-of-file error from the lexer before we
+    // ever get here, so `result` is always populated on the happy path.
+    result.ok_or(ParseError {
+        code: ErrorCode::UnexpectedEndOfFile,
+        offset: 0,
+        line: 1,
+        col: 1,
+    })
+}
 
-                obj.insert(key, val);
+// A partially-built borrowed container.
+enum PartialRef<'a> {
+    Array(Vec<ValueRef<'a>>),
+    Object(HashMap<Cow<'a, str>, ValueRef<'a>>, Option<Cow<'a, str>>),
+}
 
-                let comma_or_brace = lexer.token()?;
-                if comma_or_brace == Token::CloseBrace {
+fn place_ref<'a>(
+    stack: &mut [PartialRef<'a>],
+    result: &mut Option<ValueRef<'a>>,
+    value: ValueRef<'a>,
+) {
+    match stack.last_mut() {
+        None => *result = Some(value),
+        Some(PartialRef::Array(a)) => a.push(value),
+        Some(PartialRef::Object(o, key)) => {
+            if let Some(k) = key.take() {
+                o.insert(k, value);
+            }
+        }
+    }
+}
+
+// Parse without copying string data that has no escapes; the result borrows
+// from `s`.
+pub fn parse_borrowed(s: &str) -> Result<ValueRef<'_>, ParseError> {
+    let mut parser = StreamingParser::new(s.as_bytes());
+    let mut stack: Vec<PartialRef> = Vec::new();
+    let mut result: Option<ValueRef> = None;
+
+    for ev in &mut parser {
+        match ev? {
+            JsonEvent::NullValue => place_ref(&mut stack, &mut result, ValueRef::Null),
+            JsonEvent::BooleanValue(b) => place_ref(&mut stack, &mut result, ValueRef::Boolean(b)),
+            JsonEvent::IntegerValue(n) => place_ref(&mut stack, &mut result, ValueRef::Integer(n)),
+            JsonEvent::UIntValue(n) => place_ref(&mut stack, &mut result, ValueRef::UInt(n)),
+            JsonEvent::FloatValue(n) => place_ref(&mut stack, &mut result, ValueRef::Float(n)),
+            JsonEvent::StringValue(s) => place_ref(&mut stack, &mut result, ValueRef::String(s)),
+            JsonEvent::ArrayStart => stack.push(PartialRef::Array(Vec::new())),
+            JsonEvent::ObjectStart => stack.push(PartialRef::Object(HashMap::new(), None)),
+            JsonEvent::ObjectKey(k) => {
+                if let Some(PartialRef::Object(_, key)) = stack.last_mut() {
+                    *key = Some(k);
+                }
+            }
+            JsonEvent::ArrayEnd => {
+                if let Some(PartialRef::Array(a)) = stack.pop() {
+                    place_ref(&mut stack, &mut result, ValueRef::Array(a));
+                }
+            }
+            JsonEvent::ObjectEnd => {
+                if let Some(PartialRef::Object(o, _)) = stack.pop() {
+                    place_ref(&mut stack, &mut result, ValueRef::Object(o));
+                }
+            }
+        }
+    }
+
+    result.ok_or(ParseError {
+        code: ErrorCode::UnexpectedEndOfFile,
+        offset: 0,
+        line: 1,
+        col: 1,
+    })
+}
+
+// A tokenizer that pulls one byte at a time from an `io::Read` rather than
+// indexing a slice, so the whole document never has to live in memory.  The
+// reader is buffered internally so the per-byte cursor does not turn into a
+// syscall per byte.
+struct ReaderLexer<R: Read> {
+    bytes: std::io::Bytes<std::io::BufReader<R>>,
+    // The byte under the cursor, or None at end of input.  Loaded lazily on the
+    // first access so construction cannot fail.
+    cur: Option<u8>,
+    primed: bool,
+    pos: usize,
+    line: usize,
+    col: usize,
+}
+
+impl<R: Read> ReaderLexer<R> {
+    fn new(r: R) -> ReaderLexer<R> {
+        ReaderLexer {
+            bytes: std::io::BufReader::new(r).bytes(),
+            cur: None,
+            primed: false,
+            pos: 0,
+            line: 1,
+            col: 1,
+        }
+    }
+
+    fn error(&self, code: ErrorCode) -> ParseError {
+        ParseError {
+            code,
+            offset: self.pos,
+            line: self.line,
+            col: self.col,
+        }
+    }
+
+    fn read_raw(&mut self) -> Result<Option<u8>, ParseError> {
+        match self.bytes.next() {
+            None => Ok(None),
+            Some(Ok(b)) => Ok(Some(b)),
+            Some(Err(_)) => Err(self.error(ErrorCode::IoError)),
+        }
+    }
+
+    // Load the first byte if we have not looked at the input yet.
+    fn prime(&mut self) -> Result<(), ParseError> {
+        if !self.primed {
+            self.cur = self.read_raw()?;
+            self.primed = true;
+        }
+        Ok(())
+    }
+
+    fn eof(&self) -> bool {
+        self.cur.is_none()
+    }
+
+    fn advance(&mut self) -> Result<(), ParseError> {
+        if let Some(b) = self.cur {
+            if b == b'\n' {
+                self.line += 1;
+                self.col = 1;
+            } else {
+                self.col += 1;
+            }
+            self.pos += 1;
+        }
+        self.cur = self.read_raw()?;
+        Ok(())
+    }
+
+    fn skip_whitespace(&mut self) -> Result<(), ParseError> {
+        while matches!(self.cur, Some(b' ') | Some(b'\t') | Some(b'\r') | Some(b'\n')) {
+            self.advance()?;
+        }
+        Ok(())
+    }
+
+    fn token(&mut self) -> Result<(Token<'static>, Span), ParseError> {
+        self.prime()?;
+        self.skip_whitespace()?;
+
+        if self.eof() {
+            return Err(self.error(ErrorCode::UnexpectedEndOfFile));
+        }
+
+        let start = self.pos;
+        let line = self.line;
+        let col = self.col;
+
+        let byte = self.cur.unwrap();
+
+        let result = match byte as char {
+            '[' => {
+                self.advance()?;
+                Token::OpenBracket
+            }
+            ']' => {
+                self.advance()?;
+                Token::CloseBracket
+            }
+            ',' => {
+                self.advance()?;
+                Token::Comma
+            }
+            ':' => {
+                self.advance()?;
+                Token::Colon
+            }
+            '{' => {
+                self.advance()?;
+                Token::OpenBrace
+            }
+            '}' => {
+                self.advance()?;
+                Token::CloseBrace
+            }
+            '-' => self.lex_number()?,
+            d if d.is_ascii_digit() => self.lex_number()?,
+            '"' => self.lex_string()?,
+            _ if Lexer::is_identifier_start(byte) => self.lex_identifier()?,
+            _ => return Err(self.error(ErrorCode::UnexpectedCharacter)),
+        };
+
+        let span = Span {
+            start,
+            end: self.pos,
+            line,
+            col,
+        };
+
+        self.skip_whitespace()?;
+
+        Ok((result, span))
+    }
+
+    fn lex_identifier(&mut self) -> Result<Token<'static>, ParseError> {
+        let mut buf = Vec::new();
+        while let Some(b) = self.cur {
+            if Lexer::is_identifier_char(b) {
+                buf.push(b);
+                self.advance()?;
+            } else {
+                break;
+            }
+        }
+
+        // Only the three JSON keywords matter downstream; anything else is
+        // flattened to an empty slice so the parser reports "expected a value".
+        let ident: &'static [u8] = if buf.as_slice() == NULL_TOKEN {
+            NULL_TOKEN
+        } else if buf.as_slice() == TRUE_TOKEN {
+            TRUE_TOKEN
+        } else if buf.as_slice() == FALSE_TOKEN {
+            FALSE_TOKEN
+        } else {
+            b""
+        };
+
+        Ok(Token::Identifier(ident))
+    }
+
+    fn lex_number(&mut self) -> Result<Token<'static>, ParseError> {
+        let mut lexeme = String::new();
+
+        if self.cur == Some(b'-') {
+            lexeme.push('-');
+            self.advance()?;
+        }
+
+        if self.eof() {
+            return Err(self.error(ErrorCode::InvalidNumber));
+        }
+
+        while let Some(b) = self.cur {
+            if Lexer::is_digit(b) {
+                lexeme.push(b as char);
+                self.advance()?;
+            } else {
+                break;
+            }
+        }
+
+        let mut is_float = false;
+
+        if self.cur == Some(b'.') {
+            is_float = true;
+            lexeme.push('.');
+            self.advance()?;
+            while let Some(b) = self.cur {
+                if Lexer::is_digit(b) {
+                    lexeme.push(b as char);
+                    self.advance()?;
+                } else {
+                    break;
+                }
+            }
+        }
+
+        if self.cur == Some(b'e') || self.cur == Some(b'E') {
+            is_float = true;
+            lexeme.push(self.cur.unwrap() as char);
+            self.advance()?;
+            if self.cur == Some(b'-') || self.cur == Some(b'+') {
+                lexeme.push(self.cur.unwrap() as char);
+                self.advance()?;
+            }
+            while let Some(b) = self.cur {
+                if Lexer::is_digit(b) {
+                    lexeme.push(b as char);
+                    self.advance()?;
+                } else {
                     break;
-                } else if comma_or_brace != Token::Comma {
-                    return Err(ParseError(format!(
-                        "Expected comma or brace but got '{:?}'",
-                        comma_or_brace
-                    )));
                 }
             }
+        }
 
-            Ok(Value::Object(obj))
+        if is_float {
+            match lexeme.parse::<f64>() {
+                Ok(f) => Ok(Token::Float(f)),
+                Err(_) => Err(self.error(ErrorCode::InvalidNumber)),
+            }
+        } else if let Ok(i) = lexeme.parse::<i64>() {
+            Ok(Token::Integer(i))
+        } else if let Ok(u) = lexeme.parse::<u64>() {
+            Ok(Token::UInt(u))
+        } else {
+            match lexeme.parse::<f64>() {
+                Ok(f) => Ok(Token::Float(f)),
+                Err(_) => Err(self.error(ErrorCode::InvalidNumber)),
+            }
         }
+    }
+
+    fn lex_string(&mut self) -> Result<Token<'static>, ParseError> {
+        self.advance()?; // opening quote
+
+        let mut raw = Vec::new();
+        let mut has_escape = false;
+
+        loop {
+            match self.cur {
+                None => return Err(self.error(ErrorCode::UnexpectedEndOfFile)),
+                Some(b) => match b as char {
+                    '\n' => return Err(self.error(ErrorCode::UnexpectedCharacter)),
+                    '\\' => {
+                        // Keep both the backslash and the byte it escapes so an
+                        // escaped quote or backslash is not read as the string's
+                        // end; decode_escapes interprets them later.
+                        has_escape = true;
+                        raw.push(b);
+                        self.advance()?;
+                        match self.cur {
+                            None => return Err(self.error(ErrorCode::UnexpectedEndOfFile)),
+                            Some(escaped) => {
+                                raw.push(escaped);
+                                self.advance()?;
+                            }
+                        }
+                    }
+                    '"' => {
+                        self.advance()?;
+                        break;
+                    }
+                    _ => {
+                        raw.push(b);
+                        self.advance()?;
+                    }
+                },
+            }
+        }
+
+        // The bytes assembled here come straight off the reader and may not
+        // be valid UTF-8.
+        let s = match String::from_utf8(raw) {
+            Ok(text) if has_escape => {
+                decode_escapes(text.as_bytes()).map_err(|code| self.error(code))?
+            }
+            Ok(text) => text,
+            Err(_) => return Err(self.error(ErrorCode::InvalidUtf8)),
+        };
+
+        Ok(Token::String(Cow::Owned(s)))
+    }
+}
 
-        t => Err(ParseError(format!("Unknown token '{:?}'", t))),
+impl<R: Read> TokenStream<'static> for ReaderLexer<R> {
+    fn next_token(&mut self) -> Result<(Token<'static>, Span), ParseError> {
+        self.token()
     }
+
+    fn finish(&mut self) -> Result<(), ParseError> {
+        self.prime()?;
+        self.skip_whitespace()?;
+        if self.eof() {
+            Ok(())
+        } else {
+            Err(self.error(ErrorCode::TrailingCharacters))
+        }
+    }
+}
+
+// A pull parser reading from any `io::Read`, yielding the same JsonEvents as
+// `StreamingParser` without ever holding the whole document in memory.
+pub struct ReadStreamingParser<R: Read> {
+    machine: Machine<'static, ReaderLexer<R>>,
+}
+
+impl<R: Read> ReadStreamingParser<R> {
+    pub fn new(r: R) -> ReadStreamingParser<R> {
+        ReadStreamingParser {
+            machine: Machine::new(ReaderLexer::new(r), None),
+        }
+    }
+
+    pub fn with_max_depth(r: R, max_depth: usize) -> ReadStreamingParser<R> {
+        ReadStreamingParser {
+            machine: Machine::new(ReaderLexer::new(r), Some(max_depth)),
+        }
+    }
+
+    // The path from the document root to the cursor, valid between events.
+    pub fn stack(&self) -> &Stack {
+        &self.machine.stack
+    }
+}
+
+impl<R: Read> Iterator for ReadStreamingParser<R> {
+    type Item = Result<JsonEvent<'static>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.machine.next_event()
+    }
+}
+
+// Read a whole document from a byte source, folding the event stream back into
+// an owned Value.  The streaming machinery keeps only the current nesting path
+// resident, so this copes with documents too large to buffer as a &str.
+pub fn parse_reader<R: Read>(r: R) -> Result<Value, ParseError> {
+    parse_events(ReadStreamingParser::new(r))
 }
@@ -1,6 +1,9 @@
 use crate::fortunate_json::{
-    decode, extract_field, parse, DecodeError, FromJSON, JSONError, Value,
+    decode, encode, extract_field, parse, parse_borrowed, parse_reader, parse_with_max_depth,
+    pretty_encode, to_string, to_string_pretty, DecodeError, ErrorCode, FromJSON, JsonEvent,
+    StreamingParser, ToJSON, Value, ValueRef,
 };
+use std::borrow::Cow;
 use std::collections::hash_map::HashMap;
 
 // TODO: Like a billion tests around error conditions.
@@ -28,6 +31,24 @@ fn nested_array() {
     assert_eq!(Ok(expected), parse("[true,[false,null]]"));
 }
 
+#[test]
+fn deeply_nested() {
+    // The iterative parser handles nesting that would overflow a recursive one.
+    let depth = 10_000;
+    let input = format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+    assert!(parse(&input).is_ok());
+}
+
+#[test]
+fn max_depth_limit() {
+    // Three open brackets is fine at a limit of 3; a fourth trips the guard.
+    assert!(parse_with_max_depth("[[[1]]]", 3).is_ok());
+    assert_eq!(
+        ErrorCode::DepthLimitExceeded,
+        parse_with_max_depth("[[[[1]]]]", 3).unwrap_err().code
+    );
+}
+
 #[test]
 fn whitespace() {
     let expected = Value::Array(vec![Value::Boolean(true), Value::Boolean(false)]);
@@ -45,10 +66,35 @@ fn string() {
 #[test]
 fn busted_unicode_escape() {
     assert_eq!(
-        Err(JSONError::ParseError(
-            "Unexpected EOF when parsing unicode escape in string literal".to_owned()
-        )),
-        decode::<String>("\"\\u00\"")
+        ErrorCode::UnexpectedEndOfFile,
+        parse("\"\\u00\"").unwrap_err().code
+    );
+}
+
+#[test]
+fn unicode_escape() {
+    // A basic-plane escape and an astral-plane emoji via a surrogate pair.
+    assert_eq!(Ok(Value::String("A".to_owned())), parse("\"\\u0041\""));
+    assert_eq!(Ok(Value::String("😀".to_owned())), parse("\"\\uD83D\\uDE00\""));
+    // Every nibble of the escape contributes, so the high bits must not be lost.
+    assert_eq!(Ok(Value::String("\u{1234}".to_owned())), parse("\"\\u1234\""));
+}
+
+#[test]
+fn lone_surrogate() {
+    assert_eq!(
+        ErrorCode::InvalidUnicodeEscape,
+        parse("\"\\uD83D\"").unwrap_err().code
+    );
+    // A low surrogate with no preceding high surrogate is equally invalid.
+    assert_eq!(
+        ErrorCode::InvalidUnicodeEscape,
+        parse("\"\\uDE00\"").unwrap_err().code
+    );
+    // A high surrogate followed by a non-low-surrogate escape.
+    assert_eq!(
+        ErrorCode::InvalidUnicodeEscape,
+        parse("\"\\uD83D\\u0041\"").unwrap_err().code
     );
 }
 
@@ -66,6 +112,21 @@ fn string_with_newline() {
     assert_eq!(Ok(expected), parse("\"Hello\\nWorld\""));
 }
 
+#[test]
+fn escaped_quote_and_backslash() {
+    // The two escapes that share the string-terminator byte must round-trip
+    // without tripping the extent scanner.
+    assert_eq!(Ok(Value::String("\"".to_owned())), parse("\"\\\"\""));
+    assert_eq!(Ok(Value::String("\\".to_owned())), parse("\"\\\\\""));
+    let doc = parse("{\"k\":\"a \\\"b\\\"\"}").unwrap();
+    assert_eq!("a \"b\"", doc.as_object().unwrap()["k"].as_string().unwrap());
+    // The same input parses identically through the reader-backed path.
+    assert_eq!(
+        Ok(Value::String("\"".to_owned())),
+        parse_reader(std::io::Cursor::new(b"\"\\\"\"".to_vec()))
+    );
+}
+
 #[test]
 fn object() {
     let expected = Value::Object(HashMap::from([
@@ -76,16 +137,27 @@ fn object() {
     assert_eq!(Ok(expected), parse("{\"foo\": \"bar\", \"baz\" : true}"))
 }
 
+#[test]
+fn error_carries_position() {
+    // The colon is missing on the second line; the error points at the `1`
+    // found in its place, column 7.
+    let err = parse("{\n  \"x\" 1}").unwrap_err();
+    assert_eq!(ErrorCode::ExpectedColon, err.code);
+    assert_eq!(2, err.line);
+    assert_eq!(7, err.col);
+    assert_eq!("2:7: expected ':'", err.to_string());
+}
+
 #[test]
 fn integers() {
     let expected = Value::Array(vec![
-        Value::Number(0.0),
-        Value::Number(2.0),
-        Value::Number(4.0),
-        Value::Number(8.0),
-        Value::Number(128.0),
-        Value::Number(65535.0),
-        Value::Number(-131085.0),
+        Value::Integer(0),
+        Value::Integer(2),
+        Value::Integer(4),
+        Value::Integer(8),
+        Value::Integer(128),
+        Value::Integer(65535),
+        Value::Integer(-131085),
     ]);
 
     assert_eq!(Ok(expected), parse("[0, 2, 4 , 8, 128 \t ,65535, -131085]"));
@@ -93,18 +165,49 @@ fn integers() {
 
 #[test]
 fn float() {
-    let expected = Value::Number(3.141);
+    let expected = Value::Float(3.141);
 
     assert_eq!(Ok(expected), parse("3.141"));
 }
 
 #[test]
 fn exponential_notation() {
-    let expected = Value::Array(vec![Value::Number(1000.0), Value::Number(0.00055)]);
+    let expected = Value::Array(vec![Value::Float(1000.0), Value::Float(0.00055)]);
 
     assert_eq!(Ok(expected), parse("[1e3, 5.5e-4]"));
 }
 
+#[test]
+fn malformed_number_is_an_error() {
+    // Numeric lexemes that fail to parse must surface a clean error, not panic.
+    for src in ["[-]", "[1e]", "1E+", "[-e5]", "[-.]"] {
+        assert_eq!(
+            ErrorCode::InvalidNumber,
+            parse(src).unwrap_err().code,
+            "parsing {src:?}"
+        );
+    }
+}
+
+#[test]
+fn integer_vs_float() {
+    assert_eq!(Ok(Value::Integer(42)), parse("42"));
+    assert_eq!(Ok(Value::Float(42.0)), parse("42.0"));
+}
+
+#[test]
+fn large_integer() {
+    // Precision that an f32 would have mangled.
+    assert_eq!(Ok(Value::Integer(9007199254740993)), parse("9007199254740993"));
+}
+
+#[test]
+fn unsigned_integer() {
+    // Beyond i64::MAX but still exact in a u64.
+    assert_eq!(Ok(Value::UInt(18446744073709551615)), parse("18446744073709551615"));
+    assert_eq!(Ok(18446744073709551615u64), decode::<u64>("18446744073709551615"));
+}
+
 #[derive(Debug, PartialEq, Default)]
 struct Point {
     x: f32,
@@ -172,3 +275,143 @@ fn unpack_vec() {
 
 #[test]
 fn unpack_map() {}
+
+#[derive(Debug, PartialEq, Default, FromJSON, ToJSON)]
+struct Rgb {
+    r: u32,
+    g: u32,
+    b: u32,
+    #[json(rename = "alpha", default)]
+    a: u32,
+}
+
+#[test]
+fn derive_from_json() {
+    let parsed = parse("{\"r\": 255, \"g\": 0, \"b\": 128}").unwrap();
+    let c: Rgb = FromJSON::from_json(&parsed).unwrap();
+    // The missing "alpha" key falls back to Default.
+    assert_eq!(c, Rgb { r: 255, g: 0, b: 128, a: 0 });
+}
+
+#[test]
+fn derive_renamed_field() {
+    // The renamed key is read on the way in...
+    let parsed = parse("{\"r\": 1, \"g\": 2, \"b\": 3, \"alpha\": 200}").unwrap();
+    let c: Rgb = FromJSON::from_json(&parsed).unwrap();
+    assert_eq!(c, Rgb { r: 1, g: 2, b: 3, a: 200 });
+
+    // ...and emitted on the way back out.
+    let o = c.to_json();
+    let map = o.as_object().unwrap();
+    assert_eq!(Ok(200), map["alpha"].as_integer());
+    assert!(!map.contains_key("a"));
+}
+
+#[test]
+fn decode_error_names_field() {
+    // A field that is present but the wrong type fails with that field on the
+    // error's path.
+    let parsed = parse("{\"r\": 1, \"g\": \"oops\", \"b\": 3}").unwrap();
+    let err = <Rgb as FromJSON>::from_json(&parsed).unwrap_err();
+    assert_eq!(vec!["g".to_owned()], err.path);
+    assert_eq!("could not decode field `g`", err.to_string());
+}
+
+#[test]
+fn derive_to_json() {
+    let c = Rgb { r: 255, g: 0, b: 128, a: 255 };
+    let round = decode::<Rgb>(&to_string(&c.to_json())).unwrap();
+    assert_eq!(c, round);
+}
+
+#[test]
+fn encode_scalars() {
+    assert_eq!("null", to_string(&Value::Null));
+    assert_eq!("true", to_string(&Value::Boolean(true)));
+    assert_eq!("8", to_string(&Value::Integer(8)));
+    assert_eq!("3.141", to_string(&Value::Float(3.141)));
+}
+
+#[test]
+fn encode_escapes() {
+    let v = Value::String("a\"b\\c\nd\te".to_owned());
+    assert_eq!("\"a\\\"b\\\\c\\nd\\te\"", to_string(&v));
+}
+
+#[test]
+fn encode_pretty_array() {
+    let v = Value::Array(vec![Value::Integer(1), Value::Integer(2)]);
+    assert_eq!("[\n  1,\n  2\n]", to_string_pretty(&v, 2));
+}
+
+#[test]
+fn streaming_events() {
+    let events: Result<Vec<JsonEvent>, _> =
+        StreamingParser::new(b"{\"a\":[1,true]}").collect();
+
+    assert_eq!(
+        Ok(vec![
+            JsonEvent::ObjectStart,
+            JsonEvent::ObjectKey(Cow::Borrowed("a")),
+            JsonEvent::ArrayStart,
+            JsonEvent::IntegerValue(1),
+            JsonEvent::BooleanValue(true),
+            JsonEvent::ArrayEnd,
+            JsonEvent::ObjectEnd,
+        ]),
+        events
+    );
+}
+
+#[test]
+fn parse_from_reader() {
+    // The same documents parse from an io::Read without buffering the input.
+    let cursor = std::io::Cursor::new(b"{\"foo\": [1, true, \"bar\"]}".to_vec());
+    let expected = Value::Object(HashMap::from([(
+        "foo".to_owned(),
+        Value::Array(vec![
+            Value::Integer(1),
+            Value::Boolean(true),
+            Value::String("bar".to_owned()),
+        ]),
+    )]));
+    assert_eq!(Ok(expected), parse_reader(cursor));
+
+    // Trailing junk is still rejected.
+    assert_eq!(
+        ErrorCode::TrailingCharacters,
+        parse_reader(std::io::Cursor::new(b"1 2".to_vec()))
+            .unwrap_err()
+            .code
+    );
+
+    // A stray non-UTF-8 byte in a string literal is an error, not a panic.
+    assert_eq!(
+        ErrorCode::InvalidUtf8,
+        parse_reader(std::io::Cursor::new(vec![b'"', 0xFF, b'"']))
+            .unwrap_err()
+            .code
+    );
+}
+
+#[test]
+fn borrowed_string_is_not_copied() {
+    let input = String::from("\"hello\"");
+    let v = parse_borrowed(&input).unwrap();
+    // An escape-free literal borrows straight from the input.
+    assert_eq!(ValueRef::String(Cow::Borrowed("hello")), v);
+    assert!(matches!(v, ValueRef::String(Cow::Borrowed(_))));
+}
+
+#[test]
+fn encode_via_trait() {
+    let v = vec!["a".to_owned(), "b".to_owned()].to_json();
+    assert_eq!("[\"a\",\"b\"]", to_string(&v));
+}
+
+#[test]
+fn encode_helpers() {
+    let v = vec!["a".to_owned(), "b".to_owned()];
+    assert_eq!("[\"a\",\"b\"]", encode(&v));
+    assert_eq!("[\n  \"a\",\n  \"b\"\n]", pretty_encode(&v, 2));
+}